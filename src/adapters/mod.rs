@@ -1,5 +1,12 @@
+pub mod checkpoint;
 pub mod input;
+pub mod metrics;
+pub mod notify;
 pub mod output;
 
-pub use input::load_keys_from_txt;
-
+pub use checkpoint::Checkpoint;
+pub use input::{
+    InputOptions, InputReport, InputStats, ProxyEntry, RunFiles, is_stdin_path,
+    load_proxies_from_txt, shuffle_keys, stream_keys_from_txt, window_keys,
+};
+pub use metrics::{Metrics, serve_metrics};
@@ -0,0 +1,57 @@
+use crate::config::Notify;
+use crate::error::ValidatorError;
+use crate::validation::ValidationSummary;
+use serde_json::{Value, json};
+use std::fmt::Write;
+
+// Number of failure reasons listed in a chat message
+const TOP_REASONS: usize = 5;
+
+/// Builds the webhook body for the run summary: the raw summary for `json`,
+/// or a readable chat message for Discord and Slack
+pub fn notification_payload(
+    notify: Notify,
+    summary: &ValidationSummary,
+) -> Result<Value, ValidatorError> {
+    Ok(match notify {
+        Notify::Json => serde_json::to_value(summary)?,
+        Notify::Discord => json!({ "content": chat_message(summary, "**") }),
+        Notify::Slack => json!({ "text": chat_message(summary, "*") }),
+    })
+}
+
+// Discord and Slack only differ in the markup used for bold text
+fn chat_message(summary: &ValidationSummary, bold: &str) -> String {
+    let counts = &summary.counts;
+    let status = if summary.interrupted {
+        " (interrupted)"
+    } else {
+        ""
+    };
+    let mut message = format!(
+        "{bold}Gemini key check finished{status}{bold} in {:.2}s\n",
+        summary.duration.as_secs_f64()
+    );
+    let _ = writeln!(
+        message,
+        "Free: {} | Paid: {} | Invalid: {} | Suspended: {} | Rate limited: {} | Errored: {} | Foreign: {} | Total: {}",
+        counts.free,
+        counts.paid,
+        counts.invalid,
+        counts.suspended,
+        counts.rate_limited,
+        counts.errored,
+        counts.foreign,
+        summary.total_keys
+    );
+
+    let top_reasons = summary.top_failure_reasons(TOP_REASONS);
+    if !top_reasons.is_empty() {
+        let reasons: Vec<String> = top_reasons
+            .iter()
+            .map(|(reason, count)| format!("{reason} x{count}"))
+            .collect();
+        let _ = writeln!(message, "Top errors: {}", reasons.join(", "));
+    }
+    message.trim_end().to_string()
+}
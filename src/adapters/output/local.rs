@@ -1,30 +1,311 @@
+use crate::config::{ExportFormat, KeyCheckerConfig};
 use crate::error::ValidatorError;
-use crate::types::GeminiKey;
+use crate::types::{GeminiKey, ValidatedKey};
+use crate::validation::{RunManifest, ValidationSummary};
+use flate2::Compression;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use std::{fs, io::Write};
 use toml::Value;
 use tracing::info;
 
-// Write valid key to output file in Clewdr format
+// Files in output_dir the valid keys are exported to, per `ExportFormat`
+const CLEWDR_EXPORT_PATH: &str = "clewdr.toml";
+pub(crate) const OPENAI_PROXY_EXPORT_PATH: &str = "openai_proxy.json";
+
+/// Creates the directory this run's outputs go to: `output_dir`, or a subdirectory
+/// named after the UTC start time such as `2025-08-11T01-55` with `timestamped_runs`
+pub fn create_output_dir(config: &KeyCheckerConfig) -> Result<PathBuf, ValidatorError> {
+    let output_dir = if config.timestamped_runs {
+        config.output_dir.join(utc_timestamp(SystemTime::now()))
+    } else {
+        config.output_dir.clone()
+    };
+    fs::create_dir_all(&output_dir)?;
+    if output_dir != Path::new(".") {
+        info!("Writing results to '{}'", output_dir.display());
+    }
+    Ok(output_dir)
+}
+
+/// Whether a directory name has the shape of a timestamped run directory
+pub fn is_run_dir_name(name: &OsStr) -> bool {
+    let shape = b"0000-00-00T00-00";
+    let name = name.as_encoded_bytes();
+    name.len() == shape.len()
+        && name
+            .iter()
+            .zip(shape)
+            .all(|(byte, expected)| match expected {
+                b'0' => byte.is_ascii_digit(),
+                _ => byte == expected,
+            })
+}
+
+// Minute-precision UTC time without colons, so it is a valid file name everywhere
+fn utc_timestamp(time: SystemTime) -> String {
+    let (year, month, day, secs_of_day) = utc_date(time);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}-{:02}",
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60
+    )
+}
+
+/// Second-precision UTC time in RFC 3339, e.g. `2025-08-11T01:55:12Z`
+pub fn rfc3339(time: SystemTime) -> String {
+    let (year, month, day, secs_of_day) = utc_date(time);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+// Year, month, day and seconds into the day of a UTC time
+fn utc_date(time: SystemTime) -> (i64, i64, i64, u64) {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Civil date from days since the epoch, after Howard Hinnant's days_from_civil inverse
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day, secs_of_day)
+}
+
+/// Whether an output file is gzip-compressed, judging by its `.gz` extension
+pub fn is_gzip(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "gz")
+}
+
+/// Decodes the content of an output file, decompressing `.gz` files.
+/// Appended runs leave several gzip members in one file, which are read as one stream.
+pub fn decode_output(path: &Path, content: &[u8]) -> io::Result<String> {
+    if is_gzip(path) {
+        let mut decoded = String::new();
+        MultiGzDecoder::new(content).read_to_string(&mut decoded)?;
+        Ok(decoded)
+    } else {
+        String::from_utf8(content.to_vec())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+// Write the file through a temporary sibling that is renamed over the target once complete,
+// so a crash mid-write leaves the previous file intact
+fn write_atomically(filename: impl AsRef<Path>, content: &[u8]) -> Result<(), ValidatorError> {
+    let path = filename.as_ref();
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = path.with_file_name(format!(".{file_name}.tmp"));
+
+    let result = (|| {
+        let mut file = fs::File::create(&temp_path)?;
+        if is_gzip(path) {
+            let mut encoder = GzEncoder::new(file, Compression::default());
+            encoder.write_all(content)?;
+            file = encoder.finish()?;
+        } else {
+            file.write_all(content)?;
+        }
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    Ok(result?)
+}
+
+// Existing file content when appending, or None if there is nothing to merge into
+fn read_for_append(filename: &Path, append: bool) -> Result<Option<String>, ValidatorError> {
+    if !append {
+        return Ok(None);
+    }
+    match fs::read(filename) {
+        Ok(content) => Ok(Some(decode_output(filename, &content)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Write valid keys to output file in Clewdr format, all under a single gemini_keys array.
+// When appending, new keys are merged into the existing array and the rest of the file is kept.
 pub fn write_keys_clewdr_format(
-    file: &mut fs::File,
-    key: &GeminiKey,
+    keys: &[GeminiKey],
+    filename: &Path,
+    append: bool,
 ) -> Result<(), ValidatorError> {
-    let mut table = toml::value::Table::new();
-    table.insert("key".to_string(), Value::String(key.as_ref().to_string()));
+    let mut root: toml::value::Table = match read_for_append(filename, append)? {
+        Some(content) => toml::from_str(&content)?,
+        None => toml::value::Table::new(),
+    };
+    let mut gemini_keys = match root.remove("gemini_keys") {
+        Some(Value::Array(gemini_keys)) => gemini_keys,
+        _ => Vec::new(),
+    };
+    let existing: HashSet<String> = gemini_keys
+        .iter()
+        .filter_map(|entry| entry.get("key").and_then(Value::as_str))
+        .map(str::to_string)
+        .collect();
 
-    let gemini_keys = Value::Array(vec![Value::Table(table)]);
-    let mut root = toml::value::Table::new();
-    root.insert("gemini_keys".to_string(), gemini_keys);
+    for key in keys.iter().filter(|key| !existing.contains(key.as_ref())) {
+        let mut table = toml::value::Table::new();
+        table.insert("key".to_string(), Value::String(key.as_ref().to_string()));
+        gemini_keys.push(Value::Table(table));
+    }
+    root.insert("gemini_keys".to_string(), Value::Array(gemini_keys));
 
     let toml_string = toml::to_string(&Value::Table(root))?;
-    write!(file, "{}", toml_string)?;
+    write_atomically(filename, toml_string.as_bytes())?;
+    info!(
+        "File '{}' created with {} keys",
+        filename.display(),
+        keys.len()
+    );
     Ok(())
 }
 
-// Write keys to a text file with custom filename
-pub fn write_keys_to_file(keys: &[String], filename: &str) -> Result<(), ValidatorError> {
-    let content = keys.join("\n");
-    fs::write(filename, content)?;
-    info!("File '{}' created with {} keys", filename, keys.len());
+// Write valid keys for gemini-to-OpenAI proxies, as `{ "gemini": ["AIzaSy...", ...] }`.
+// When appending, new keys are merged into the existing array and the other fields are kept.
+pub fn write_keys_openai_proxy_format(
+    keys: &[GeminiKey],
+    filename: &Path,
+    append: bool,
+) -> Result<(), ValidatorError> {
+    let mut root: serde_json::Map<String, serde_json::Value> =
+        match read_for_append(filename, append)? {
+            Some(content) => serde_json::from_str(&content)?,
+            None => serde_json::Map::new(),
+        };
+    let mut gemini = match root.remove("gemini") {
+        Some(serde_json::Value::Array(gemini)) => gemini,
+        _ => Vec::new(),
+    };
+    let existing: HashSet<String> = gemini
+        .iter()
+        .filter_map(serde_json::Value::as_str)
+        .map(str::to_string)
+        .collect();
+
+    for key in keys.iter().filter(|key| !existing.contains(key.as_ref())) {
+        gemini.push(serde_json::Value::String(key.as_ref().to_string()));
+    }
+    root.insert("gemini".to_string(), serde_json::Value::Array(gemini));
+
+    let content = serde_json::to_string_pretty(&root)?;
+    write_atomically(filename, content.as_bytes())?;
+    info!(
+        "File '{}' created with {} keys",
+        filename.display(),
+        keys.len()
+    );
     Ok(())
 }
+
+/// Exports the valid keys of a run to the file of `format` in `output_dir`
+pub fn export_valid_keys(
+    format: ExportFormat,
+    keys: &[GeminiKey],
+    output_dir: &Path,
+    append: bool,
+) -> Result<(), ValidatorError> {
+    match format {
+        ExportFormat::Clewdr => {
+            write_keys_clewdr_format(keys, &output_dir.join(CLEWDR_EXPORT_PATH), append)
+        }
+        ExportFormat::OpenaiProxy => {
+            write_keys_openai_proxy_format(keys, &output_dir.join(OPENAI_PROXY_EXPORT_PATH), append)
+        }
+    }
+}
+
+// Write validated keys with their tier and outcome to a JSON file.
+// When appending, earlier results for keys validated again are replaced by the new ones.
+pub fn write_validated_keys_json(
+    keys: &[ValidatedKey],
+    filename: &Path,
+    append: bool,
+) -> Result<(), ValidatorError> {
+    let mut results: Vec<serde_json::Value> = match read_for_append(filename, append)? {
+        Some(content) => serde_json::from_str(&content)?,
+        None => Vec::new(),
+    };
+    let validated: HashSet<&str> = keys.iter().map(|key| key.key.as_ref()).collect();
+    results.retain(|result| {
+        result
+            .get("key")
+            .and_then(serde_json::Value::as_str)
+            .is_none_or(|key| !validated.contains(key))
+    });
+    for key in keys {
+        results.push(serde_json::to_value(key)?);
+    }
+
+    let content = serde_json::to_string_pretty(&results)?;
+    write_atomically(filename, content.as_bytes())?;
+    info!(
+        "File '{}' created with {} keys",
+        filename.display(),
+        keys.len()
+    );
+    Ok(())
+}
+
+// Write the run summary, including latency percentiles, to a JSON file
+pub fn write_summary_json(
+    summary: &ValidationSummary,
+    filename: &Path,
+) -> Result<(), ValidatorError> {
+    let content = serde_json::to_string_pretty(summary)?;
+    write_atomically(filename, content.as_bytes())?;
+    info!("File '{}' created with the run summary", filename.display());
+    Ok(())
+}
+
+pub fn write_manifest_json(manifest: &RunManifest, filename: &Path) -> Result<(), ValidatorError> {
+    let content = serde_json::to_string_pretty(manifest)?;
+    write_atomically(filename, content.as_bytes())?;
+    info!(
+        "File '{}' created with the run manifest",
+        filename.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::mock_server::temp_dir;
+
+    #[test]
+    fn failed_write_leaves_the_previous_file_intact() {
+        let dir = temp_dir("atomic_write");
+        let path = dir.join("keys.txt");
+        write_atomically(&path, b"previous").unwrap();
+        assert!(!dir.join(".keys.txt.tmp").exists());
+
+        // A directory in the way of the temporary file fails the write before the rename
+        fs::create_dir(dir.join(".keys.txt.tmp")).unwrap();
+        assert!(write_atomically(&path, b"partial").is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "previous");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,3 +1,7 @@
 pub mod local;
+pub mod sqlite;
+pub mod webhook;
 
 pub use local::*;
+pub use sqlite::SqliteSink;
+pub use webhook::post_webhook;
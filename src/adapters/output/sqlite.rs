@@ -0,0 +1,103 @@
+use crate::error::ValidatorError;
+use crate::types::ValidatedKey;
+use rusqlite::{Connection, params};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+// Results are inserted in one transaction per batch of this many keys
+const BATCH_SIZE: usize = 500;
+
+const CREATE_TABLE: &str = "CREATE TABLE IF NOT EXISTS validated_keys (
+    id INTEGER PRIMARY KEY,
+    run_id TEXT NOT NULL,
+    key TEXT NOT NULL,
+    tier TEXT NOT NULL,
+    latency_ms INTEGER NOT NULL,
+    error TEXT,
+    reason TEXT,
+    retries INTEGER NOT NULL,
+    validated_at INTEGER NOT NULL
+)";
+
+const INSERT_KEY: &str = "INSERT INTO validated_keys
+    (run_id, key, tier, latency_ms, error, reason, retries, validated_at)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)";
+
+/// Accumulates validated keys of a run into a SQLite database shared across runs.
+/// Keys are stored masked, or as their SHA-256 with `store_key_hash`, so the database
+/// never holds usable keys.
+pub struct SqliteSink {
+    conn: Connection,
+    // Identifies this run's rows, from its start time and process id
+    run_id: String,
+    // Store the SHA-256 of each key instead of its masked form
+    store_key_hash: bool,
+    // Keys waiting for the next batch insert, with their validation time in unix seconds
+    pending: Vec<(ValidatedKey, i64)>,
+}
+
+impl SqliteSink {
+    pub fn open(path: &Path, store_key_hash: bool) -> Result<Self, ValidatorError> {
+        let conn = Connection::open(path)?;
+        conn.execute(CREATE_TABLE, [])?;
+        let run_id = format!("{}-{}", unix_time().as_millis(), std::process::id());
+        info!(
+            "Recording results in '{}' as run {}",
+            path.display(),
+            run_id
+        );
+        Ok(Self {
+            conn,
+            run_id,
+            store_key_hash,
+            pending: Vec::with_capacity(BATCH_SIZE),
+        })
+    }
+
+    pub fn record(&mut self, validated_key: &ValidatedKey) -> Result<(), ValidatorError> {
+        self.pending
+            .push((validated_key.clone(), unix_time().as_secs() as i64));
+        if self.pending.len() >= BATCH_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Inserts the pending keys in a single transaction
+    pub fn flush(&mut self) -> Result<(), ValidatorError> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let tx = self.conn.transaction()?;
+        {
+            let mut insert = tx.prepare_cached(INSERT_KEY)?;
+            for (validated_key, validated_at) in &self.pending {
+                let key = if self.store_key_hash {
+                    validated_key.key.sha256()
+                } else {
+                    validated_key.key.always_masked()
+                };
+                insert.execute(params![
+                    self.run_id,
+                    key,
+                    format!("{:?}", validated_key.tier),
+                    validated_key.latency_ms as i64,
+                    validated_key.error,
+                    validated_key.reason,
+                    validated_key.retries as i64,
+                    validated_at,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+fn unix_time() -> std::time::Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
@@ -0,0 +1,26 @@
+use crate::error::ValidatorError;
+use reqwest::Client;
+use serde_json::Value;
+use tracing::info;
+use url::Url;
+
+// POST the notification payload as JSON to the configured webhook
+pub async fn post_webhook(
+    client: &Client,
+    url: &Url,
+    payload: &Value,
+) -> Result<(), ValidatorError> {
+    // Webhook URLs usually embed a secret token, so it is stripped from errors
+    client
+        .post(url.clone())
+        .json(payload)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| ValidatorError::from(e.without_url()))?;
+    info!(
+        "Run summary sent to webhook at {}",
+        url.host_str().unwrap_or_default()
+    );
+    Ok(())
+}
@@ -1,3 +1,7 @@
+#[allow(clippy::module_inception)]
 mod config;
 
-pub use config::KeyCheckerConfig;
+pub use config::{
+    ApiFlavor, AuthMode, Command, ExportFormat, InputFormat, IpFamily, KeyCheckerConfig, LogFormat,
+    Notify, OutputFormat, ProbeEndpoint, RetryOn, Shard, TierStrategy,
+};
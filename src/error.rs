@@ -1,4 +1,29 @@
+use crate::config::RetryOn;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error as _;
+use std::sync::LazyLock;
 use thiserror::Error;
+use url::Url;
+
+// Lowercase fragments of rustls error messages, e.g. `invalid peer certificate: UnknownIssuer`,
+// or `received corrupt message` when the host does not speak TLS on that port
+const TLS_ERROR_MARKERS: [&str; 6] = [
+    "certificate",
+    "tls",
+    "handshake",
+    "corrupt message",
+    "peer is incompatible",
+    "peer misbehaved",
+];
+
+// Fragments of errors raised by HTTP CONNECT tunnels and SOCKS proxies
+const PROXY_ERROR_MARKERS: [&str; 3] = ["proxy", "socks", "tunnel"];
+
+// Project number in a message, e.g. `API has not been used in project 123456789 before`
+static MESSAGE_PROJECT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\bprojects?[ /]([0-9]+)\b").unwrap());
 
 #[derive(Error, Debug)]
 pub enum ValidatorError {
@@ -17,18 +42,113 @@ pub enum ValidatorError {
     #[error("TOML deserialization error: {0}")]
     TomlDe(#[from] toml::de::Error),
 
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
 
+    #[error("Invalid test body file '{}': {source}", path.display())]
+    TestBodyInvalid {
+        path: std::path::PathBuf,
+        source: serde_json::Error,
+    },
+
+    #[error("Invalid JSON input file '{}': {source}", path.display())]
+    InputJsonInvalid {
+        path: std::path::PathBuf,
+        source: serde_json::Error,
+    },
+
+    #[error("Invalid TOML input file '{}': {source}", path.display())]
+    InputTomlInvalid {
+        path: std::path::PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[error("Invalid regex: {0}")]
+    Regex(#[from] regex::Error),
+
+    #[error("Invalid glob pattern: {0}")]
+    GlobPattern(#[from] glob::PatternError),
+
+    #[error("'{}' has lines that are not keys: {lines}", path.display())]
+    MalformedInput {
+        path: std::path::PathBuf,
+        lines: String,
+    },
+
+    #[error("No input files found at '{0}'")]
+    NoInputFiles(String),
+
     #[error("URL parse error: {0}")]
     UrlParse(#[from] url::ParseError),
 
+    #[error("Unsupported proxy scheme '{0}', expected http, https, socks5 or socks5h")]
+    UnsupportedProxyScheme(String),
+
+    #[error("Invalid proxy list '{}': {reason}", path.display())]
+    ProxyListInvalid {
+        path: std::path::PathBuf,
+        reason: String,
+    },
+
+    #[error("Proxy health check failed for {0}")]
+    ProxyUnhealthy(String),
+
+    #[error("API host unreachable, {0}")]
+    ApiHostUnreachable(String),
+
+    #[error(
+        "Aborted after {0} consecutive keys failed with network or server errors, check the network, proxy and api_host"
+    )]
+    TooManyConsecutiveFailures(usize),
+
+    #[error("Invalid CA certificate '{}': {reason}", path.display())]
+    CaCertInvalid {
+        path: std::path::PathBuf,
+        reason: String,
+    },
+
+    #[error("Invalid client identity: {0}")]
+    ClientIdentityInvalid(String),
+
+    #[error("'{}' already exists, pass --force to overwrite it", .0.display())]
+    ConfigExists(std::path::PathBuf),
+
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    #[error("The vertex API flavor requires `{0}` to be set")]
+    VertexSettingMissing(&'static str),
+
+    #[error("Invalid custom header '{name}': {reason}")]
+    InvalidHeader { name: String, reason: String },
+
+    // `reason` carries Google's machine reason, e.g. API_KEY_INVALID, when the body had one,
+    // and `project` the project it named, see `project_hint`
     #[error("Key is unavailable or invalid")]
-    KeyInvalid,
+    KeyInvalid {
+        reason: Option<String>,
+        project: Option<String>,
+    },
+
+    #[error("Key is suspended or its project has the API disabled")]
+    KeySuspended {
+        reason: Option<String>,
+        project: Option<String>,
+    },
 
     #[error("Invalid Google API key format: {0}")]
     KeyFormatInvalid(String),
 
+    // `line` is the input line, shaped like a key of `provider`
+    #[error("{provider} key, not a Gemini key")]
+    ForeignKey {
+        provider: &'static str,
+        line: String,
+    },
+
     #[error("HTTP 400 Bad Request: {body}")]
     HttpBadRequest { body: String },
 
@@ -38,8 +158,12 @@ pub enum ValidatorError {
     #[error("HTTP 403 Forbidden: {body}")]
     HttpForbidden { body: String },
 
+    // `retry_after` is the delay requested by the server, if any
     #[error("HTTP 429 Too Many Requests: {body}")]
-    HttpTooManyRequests { body: String },
+    HttpTooManyRequests {
+        body: String,
+        retry_after: Option<std::time::Duration>,
+    },
 
     #[error("HTTP {status} Client Error: {body}")]
     HttpClientError { status: u16, body: String },
@@ -60,4 +184,293 @@ impl From<figment::Error> for ValidatorError {
     }
 }
 
+impl ValidatorError {
+    /// Class of failures worth retrying: network-level errors (timeouts, connection
+    /// resets, proxy failures), rate limiting and upstream server errors.
+    /// Returns None for failures that would only fail again, such as a 400.
+    pub fn retry_class(&self) -> Option<RetryOn> {
+        match self {
+            ValidatorError::ReqwestError(e)
+                if e.is_timeout() || e.is_connect() || e.is_request() =>
+            {
+                Some(RetryOn::Transient)
+            }
+            ValidatorError::HttpTooManyRequests { .. } => Some(RetryOn::RateLimited),
+            ValidatorError::HttpServerError { .. } => Some(RetryOn::ServerError),
+            _ => None,
+        }
+    }
+
+    /// Short category naming the kind of failure, recorded with errored keys.
+    /// Transport failures are told apart by the messages of the error's sources, since
+    /// reqwest only flags them as connect errors: `dns`, `tls`, `proxy`, `timeout`,
+    /// `connect_refused` and `connect`. The error's own message is left out, as the
+    /// request URL in it may contain any of those words.
+    pub fn category(&self) -> &'static str {
+        match self {
+            ValidatorError::ReqwestError(e) => {
+                let chain = source_chain(e).join(": ").to_lowercase();
+                if chain.contains("dns error") || chain.contains("failed to lookup") {
+                    "dns"
+                } else if TLS_ERROR_MARKERS
+                    .iter()
+                    .any(|marker| chain.contains(marker))
+                {
+                    "tls"
+                } else if PROXY_ERROR_MARKERS
+                    .iter()
+                    .any(|marker| chain.contains(marker))
+                {
+                    "proxy"
+                } else if e.is_timeout() {
+                    "timeout"
+                } else if chain.contains("connection refused") {
+                    "connect_refused"
+                } else if e.is_connect() {
+                    "connect"
+                } else if e.is_body() || e.is_decode() {
+                    "response"
+                } else {
+                    "request"
+                }
+            }
+            ValidatorError::KeyInvalid { .. }
+            | ValidatorError::HttpBadRequest { .. }
+            | ValidatorError::HttpUnauthorized { .. } => "invalid",
+            ValidatorError::KeySuspended { .. } | ValidatorError::HttpForbidden { .. } => {
+                "suspended"
+            }
+            ValidatorError::KeyFormatInvalid(_) => "key_format",
+            ValidatorError::ForeignKey { .. } => "foreign_key",
+            ValidatorError::HttpTooManyRequests { .. } => "rate_limited",
+            ValidatorError::HttpClientError { .. } => "client_error",
+            ValidatorError::HttpServerError { .. } => "server_error",
+            _ => "other",
+        }
+    }
+
+    /// Innermost source of an HTTP error, e.g. `Connection refused (os error 111)`, which
+    /// reqwest leaves out of its own `error sending request` message
+    pub fn root_cause(&self) -> Option<String> {
+        match self {
+            ValidatorError::ReqwestError(e) => {
+                source_chain(e).pop().or_else(|| Some(e.to_string()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the request timed out, whatever category its error chain falls in
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, ValidatorError::ReqwestError(e) if e.is_timeout())
+    }
+
+    /// Whether the request never got a response: the connection failed or timed out
+    pub fn is_connection_error(&self) -> bool {
+        matches!(self, ValidatorError::ReqwestError(e) if e.is_connect() || e.is_timeout())
+    }
+
+    /// Machine-readable reason from Google's error body, e.g. `API_KEY_INVALID`
+    pub fn google_reason(&self) -> Option<String> {
+        match self {
+            ValidatorError::KeyInvalid { reason, .. }
+            | ValidatorError::KeySuspended { reason, .. } => reason.clone(),
+            ValidatorError::HttpBadRequest { body }
+            | ValidatorError::HttpUnauthorized { body }
+            | ValidatorError::HttpForbidden { body }
+            | ValidatorError::HttpTooManyRequests { body, .. }
+            | ValidatorError::HttpClientError { body, .. }
+            | ValidatorError::HttpServerError { body, .. } => {
+                GoogleError::parse(body).map(|error| error.reason().to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// Google Cloud project behind the key, when the error body names it
+    pub fn project_hint(&self) -> Option<String> {
+        match self {
+            ValidatorError::KeyInvalid { project, .. }
+            | ValidatorError::KeySuspended { project, .. } => project.clone(),
+            ValidatorError::HttpBadRequest { body }
+            | ValidatorError::HttpUnauthorized { body }
+            | ValidatorError::HttpForbidden { body }
+            | ValidatorError::HttpTooManyRequests { body, .. }
+            | ValidatorError::HttpClientError { body, .. }
+            | ValidatorError::HttpServerError { body, .. } => GoogleError::parse(body)?.project(),
+            _ => None,
+        }
+    }
+
+    /// Quota exhausted according to a 429 body, when it names one
+    pub fn quota_violation(&self) -> Option<QuotaViolation> {
+        match self {
+            ValidatorError::HttpTooManyRequests { body, .. } => {
+                GoogleError::parse(body)?.quota_violation().cloned()
+            }
+            _ => None,
+        }
+    }
+}
+
+// Messages of the sources of the error, outermost first
+fn source_chain(error: &reqwest::Error) -> Vec<String> {
+    let mut causes = Vec::new();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        causes.push(cause.to_string());
+        source = cause.source();
+    }
+    causes
+}
+
+/// Error object returned by Google APIs, e.g.
+/// `{"error": {"code": 400, "message": "...", "status": "INVALID_ARGUMENT", "details": [...]}}`
+#[derive(Debug, Clone, Deserialize)]
+pub struct GoogleError {
+    #[serde(default)]
+    pub code: u16,
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    details: Vec<GoogleErrorDetail>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct GoogleErrorDetail {
+    // Set on google.rpc.ErrorInfo details, whose metadata may name the consumer project
+    #[serde(default)]
+    reason: Option<String>,
+    #[serde(default)]
+    metadata: BTreeMap<String, serde_json::Value>,
+    // Set on google.rpc.Help details, e.g. a console link with `?project=`
+    #[serde(default)]
+    links: Vec<HelpLink>,
+    // Set on google.rpc.RetryInfo details, e.g. "37s"
+    #[serde(default, rename = "retryDelay")]
+    retry_delay: Option<String>,
+    // Set on google.rpc.QuotaFailure details
+    #[serde(default)]
+    violations: Vec<QuotaViolation>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct HelpLink {
+    #[serde(default)]
+    url: String,
+}
+
+/// Quota named by a QuotaFailure detail of a 429 response
+#[derive(Debug, Clone, Deserialize)]
+pub struct QuotaViolation {
+    // e.g. generativelanguage.googleapis.com/generate_content_free_tier_requests
+    #[serde(default, rename = "quotaMetric")]
+    pub metric: Option<String>,
+    // Names the window, e.g. GenerateRequestsPerDayPerProjectPerModel-FreeTier
+    #[serde(default, rename = "quotaId")]
+    pub quota_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct GoogleErrorEnvelope {
+    error: GoogleError,
+}
+
+impl GoogleError {
+    /// Parses a response body, returning None when it is not a Google error object
+    pub fn parse(body: &str) -> Option<Self> {
+        serde_json::from_str::<GoogleErrorEnvelope>(body)
+            .ok()
+            .map(|envelope| envelope.error)
+    }
+
+    /// Delay requested by a RetryInfo detail
+    pub fn retry_delay(&self) -> Option<std::time::Duration> {
+        self.details
+            .iter()
+            .filter_map(|detail| detail.retry_delay.as_deref())
+            .find_map(|delay| delay.strip_suffix('s')?.parse::<f64>().ok())
+            .and_then(|secs| std::time::Duration::try_from_secs_f64(secs).ok())
+    }
+
+    /// First quota violation listed in a QuotaFailure detail
+    pub fn quota_violation(&self) -> Option<&QuotaViolation> {
+        self.details
+            .iter()
+            .find_map(|detail| detail.violations.first())
+    }
+
+    /// Project the error names: the `projects/<id>` consumer of an ErrorInfo, else the
+    /// `project` parameter of a Help link, else a project number in the message
+    pub fn project(&self) -> Option<String> {
+        let consumer = self.details.iter().find_map(|detail| {
+            let consumer = detail.metadata.get("consumer")?.as_str()?;
+            consumer.strip_prefix("projects/").map(str::to_string)
+        });
+        let link = || {
+            self.details
+                .iter()
+                .flat_map(|detail| &detail.links)
+                .filter_map(|link| Url::parse(&link.url).ok())
+                .find_map(|url| {
+                    url.query_pairs()
+                        .find(|(name, _)| name == "project")
+                        .map(|(_, project)| project.into_owned())
+                })
+        };
+        let message = || {
+            MESSAGE_PROJECT
+                .captures(&self.message)
+                .map(|captures| captures[1].to_string())
+        };
+        consumer.or_else(link).or_else(message)
+    }
+
+    /// The most specific reason available: the ErrorInfo reason, else the status
+    pub fn reason(&self) -> &str {
+        self.details
+            .iter()
+            .find_map(|detail| detail.reason.as_deref())
+            .unwrap_or(&self.status)
+    }
+}
+
 pub type Result<T> = std::result::Result<T, ValidatorError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+
+    // Host whose name holds every proxy marker, resolved to `addr`
+    const MARKED_HOST: &str = "tls-socks-tunnel.gemini-proxy.example.com";
+
+    async fn request_error(addr: SocketAddr) -> ValidatorError {
+        let client = reqwest::Client::builder()
+            .no_proxy()
+            .resolve(MARKED_HOST, addr)
+            .timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+        let url = format!("http://{MARKED_HOST}:{}/v1beta/models", addr.port());
+        client.get(url).send().await.unwrap_err().into()
+    }
+
+    #[tokio::test]
+    async fn category_ignores_markers_in_the_request_url() {
+        // Nothing listens on a port whose listener was dropped
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = closed.local_addr().unwrap();
+        drop(closed);
+        assert_eq!(request_error(addr).await.category(), "connect_refused");
+
+        // Accepts connections but never answers
+        let silent = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let error = request_error(silent.local_addr().unwrap()).await;
+        assert_eq!(error.category(), "timeout");
+    }
+}
@@ -1,39 +1,145 @@
+use gemini_keychecker::config::{Command, KeyCheckerConfig, LogFormat, OutputFormat};
 use gemini_keychecker::error::ValidatorError;
-use gemini_keychecker::{BANNER, config::KeyCheckerConfig, validation::start_validation};
+use gemini_keychecker::types::KeyMask;
+use gemini_keychecker::utils::{Color, paint, set_color_enabled};
+use gemini_keychecker::{BANNER, validation::start_validation};
 use mimalloc::MiMalloc;
-use tracing::info;
+use std::process::ExitCode;
+use tracing::{info, warn};
 use tracing_indicatif::IndicatifLayer;
 use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
+// Exit status when fewer keys than `fail_under` validated as free or paid
+const EXIT_TOO_FEW_VALID: u8 = 3;
+// Exit status when the failure rate exceeded `max_failure_rate`
+const EXIT_FAILURE_RATE: u8 = 4;
+
 /// Main function - displays banner and starts validation service
 #[tokio::main]
-async fn main() -> Result<(), ValidatorError> {
+async fn main() -> Result<ExitCode, ValidatorError> {
     let config = KeyCheckerConfig::load_config()?;
+    match &config.command {
+        Some(Command::Init { path, force }) => {
+            KeyCheckerConfig::write_template(path, *force)?;
+            println!("Wrote the default configuration to {}", path.display());
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::Completions { shell }) => {
+            KeyCheckerConfig::write_completions(*shell, &mut std::io::stdout());
+            return Ok(ExitCode::SUCCESS);
+        }
+        None => {}
+    }
+    if config.print_config {
+        print!("{}", config.redacted_toml()?);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    KeyMask {
+        reveal: config.log_full_keys,
+        ..config.log_key_mask
+    }
+    .install();
+
+    // NDJSON results own stdout, so the banner and summary go to stderr with the logs
+    let report_to_stderr = config.output_format == OutputFormat::Ndjson;
+
+    // Colors are decided separately for the banner and summary, and for stderr (logs)
+    set_color_enabled(if report_to_stderr {
+        config.use_color(&std::io::stderr())
+    } else {
+        config.use_color(&std::io::stdout())
+    });
+    let log_ansi = config.use_color(&std::io::stderr());
 
-    let indicatif_layer = IndicatifLayer::new();
+    // Logs go to stderr, through the progress bar writer when it is shown
+    let indicatif_layer = config.show_progress().then(IndicatifLayer::new);
+    let log_writer = match &indicatif_layer {
+        Some(layer) => BoxMakeWriter::new(layer.get_stderr_writer()),
+        None => BoxMakeWriter::new(std::io::stderr),
+    };
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&config.log_level));
 
+    // Exactly one of the text and JSON formatters is installed
+    let (text_layer, json_layer) = match config.log_format {
+        LogFormat::Text => (
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .with_writer(log_writer)
+                    .with_ansi(log_ansi)
+                    .with_level(true)
+                    .with_target(false),
+            ),
+            None,
+        ),
+        LogFormat::Json => (
+            None,
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_writer(log_writer)
+                    .with_level(true)
+                    .with_target(true),
+            ),
+        ),
+    };
+
     tracing_subscriber::registry()
         .with(env_filter)
-        .with(
-            tracing_subscriber::fmt::layer()
-                .with_writer(indicatif_layer.get_stderr_writer())
-                .with_level(true)
-                .with_target(false),
-        )
+        .with(text_layer)
+        .with(json_layer)
         .with(indicatif_layer)
         .init();
 
+    if config.log_full_keys {
+        warn!("Full key logging is enabled: API keys will appear in plaintext in the logs");
+    }
+
     // Display banner and configuration status at startup
-    println!("{BANNER}");
+    let banner = paint(BANNER, Color::Cyan);
+    if report_to_stderr {
+        eprintln!("{banner}");
+    } else {
+        println!("{banner}");
+    }
     info!("Configuration loaded: {}", config);
 
-    // Start validation service
-    start_validation().await
+    // Start validation service and print the results
+    let Some(summary) = start_validation().await? else {
+        return Ok(ExitCode::SUCCESS);
+    };
+    if report_to_stderr {
+        eprintln!("{summary}");
+    } else {
+        println!("{summary}");
+    }
+
+    // Let scripts tell a run that found nothing usable from a successful one
+    if summary.valid_keys() < config.fail_under {
+        warn!(
+            "Found {} valid keys, fewer than the required {}",
+            summary.valid_keys(),
+            config.fail_under
+        );
+        return Ok(ExitCode::from(EXIT_TOO_FEW_VALID));
+    }
+    if let Some(max_failure_rate) = config.max_failure_rate
+        && summary.failure_rate() > max_failure_rate
+    {
+        warn!(
+            "Failure rate {:.1}% exceeds the allowed {:.1}%",
+            summary.failure_rate() * 100.0,
+            max_failure_rate * 100.0
+        );
+        return Ok(ExitCode::from(EXIT_FAILURE_RATE));
+    }
+
+    Ok(ExitCode::SUCCESS)
 }
@@ -1,11 +1,22 @@
 use crate::error::ValidatorError;
+use crate::utils::Attempts;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
 use std::str::FromStr;
-use std::sync::LazyLock;
+use std::sync::{Arc, LazyLock, OnceLock};
+use std::time::Duration;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
 pub struct GeminiKey {
     pub inner: String,
+    // Input file the key was read from
+    #[serde(skip)]
+    pub source: Option<Arc<Path>>,
 }
 
 impl AsRef<str> for GeminiKey {
@@ -14,34 +25,251 @@ impl AsRef<str> for GeminiKey {
     }
 }
 
-impl FromStr for GeminiKey {
-    type Err = ValidatorError;
-    fn from_str(s: &str) -> Result<Self, ValidatorError> {
-        static RE: LazyLock<Regex> =
-            LazyLock::new(|| Regex::new(r"^AIzaSy[A-Za-z0-9_-]{33}$").unwrap());
+// Built-in shape of a Google API key
+static KEY_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^AIzaSy[A-Za-z0-9_-]{33}$").unwrap());
+
+// Shape of a Google OAuth access token, used for credentials sent as bearer tokens
+pub static ACCESS_TOKEN_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^ya29\.[A-Za-z0-9_.-]+$").unwrap());
+
+// Key shapes of other providers that get pasted into Gemini key lists, so they are reported
+// instead of validated. Checked in order, so more specific prefixes come first.
+static FOREIGN_KEY_SHAPES: LazyLock<Vec<(&str, Regex)>> = LazyLock::new(|| {
+    [
+        ("Anthropic", r"^sk-ant-[A-Za-z0-9_-]{20,}$"),
+        ("OpenRouter", r"^sk-or-v1-[0-9a-f]{64}$"),
+        ("OpenAI", r"^sk-(proj-|svcacct-|admin-)?[A-Za-z0-9_-]{20,}$"),
+        ("Groq", r"^gsk_[A-Za-z0-9]{52}$"),
+        ("xAI", r"^xai-[A-Za-z0-9]{80}$"),
+        ("Hugging Face", r"^hf_[A-Za-z0-9]{34}$"),
+        (
+            "GitHub",
+            r"^(gh[pousr]_[A-Za-z0-9]{36}|github_pat_[A-Za-z0-9_]{82})$",
+        ),
+        ("AWS", r"^(AKIA|ASIA)[0-9A-Z]{16}$"),
+    ]
+    .into_iter()
+    .map(|(provider, pattern)| (provider, Regex::new(pattern).unwrap()))
+    .collect()
+});
 
+/// Provider whose key shape the line has, for keys of other services found in the input
+pub fn foreign_key_provider(line: &str) -> Option<&'static str> {
+    FOREIGN_KEY_SHAPES
+        .iter()
+        .find(|(_, pattern)| pattern.is_match(line))
+        .map(|(provider, _)| *provider)
+}
+
+impl GeminiKey {
+    /// Parses a key, checking its shape against `pattern` instead of the built-in one.
+    /// Lines failing the check that look like another provider's key are `ForeignKey`.
+    pub fn parse_with(s: &str, pattern: &Regex) -> Result<Self, ValidatorError> {
         let cleaned = s.trim();
 
-        if RE.is_match(cleaned) {
+        if pattern.is_match(cleaned) {
             Ok(Self {
                 inner: cleaned.to_string(),
+                source: None,
+            })
+        } else if let Some(provider) = foreign_key_provider(cleaned) {
+            Err(ValidatorError::ForeignKey {
+                provider,
+                line: cleaned.to_string(),
             })
         } else {
             Err(ValidatorError::KeyFormatInvalid(cleaned.to_string()))
         }
     }
+
+    pub fn with_source(mut self, source: Arc<Path>) -> Self {
+        self.source = Some(source);
+        self
+    }
+}
+
+/// How many characters of a key are shown when it is logged
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct KeyMask {
+    // Leading characters kept visible
+    #[serde(default)]
+    pub prefix: usize,
+    // Trailing characters kept visible
+    #[serde(default)]
+    pub suffix: usize,
+    // Log keys in plaintext. Only set through the explicit `log_full_keys` opt-in.
+    #[serde(skip)]
+    pub reveal: bool,
+}
+
+impl Default for KeyMask {
+    fn default() -> Self {
+        Self {
+            prefix: 10,
+            suffix: 0,
+            reveal: false,
+        }
+    }
+}
+
+// Mask applied by `GeminiKey::masked`, installed once at startup
+static KEY_MASK: OnceLock<KeyMask> = OnceLock::new();
+
+impl KeyMask {
+    /// Makes this the mask used for every logged key. Only the first call has an effect.
+    pub fn install(self) {
+        let _ = KEY_MASK.set(self);
+    }
+
+    /// Masks the key, always hiding at least one character unless `reveal` is set
+    pub fn apply(&self, key: &str) -> String {
+        if self.reveal {
+            return key.to_string();
+        }
+        let chars: Vec<char> = key.chars().collect();
+        let prefix = self.prefix.min(chars.len().saturating_sub(1));
+        let suffix = self.suffix.min(chars.len().saturating_sub(prefix + 1));
+        let head: String = chars[..prefix].iter().collect();
+        let tail: String = chars[chars.len() - suffix..].iter().collect();
+        format!("{head}...{tail}")
+    }
+}
+
+impl GeminiKey {
+    /// The key as it should appear in logs, masked by the installed `KeyMask`
+    pub fn masked(&self) -> String {
+        KEY_MASK.get_or_init(KeyMask::default).apply(&self.inner)
+    }
+
+    /// The key masked by the installed `KeyMask`, even when that reveals logged keys,
+    /// for records that outlive the run
+    pub fn always_masked(&self) -> String {
+        let mask = KEY_MASK.get_or_init(KeyMask::default);
+        KeyMask {
+            reveal: false,
+            ..*mask
+        }
+        .apply(&self.inner)
+    }
+
+    /// Hex SHA-256 of the key, for records that must identify a key without holding it
+    pub fn sha256(&self) -> String {
+        format!("{:x}", Sha256::digest(self.inner.as_bytes()))
+    }
+}
+
+impl FromStr for GeminiKey {
+    type Err = ValidatorError;
+    fn from_str(s: &str) -> Result<Self, ValidatorError> {
+        Self::parse_with(s, &KEY_PATTERN)
+    }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum KeyTier {
     Free,
     Paid,
+    Invalid,
+    // Key exists but is suspended or blocked (HTTP 403)
+    Suspended,
+    RateLimited,
+    // Request kept failing for transient reasons after exhausting retries
+    Errored,
+    // Key of another provider, e.g. OpenAI, recorded without a request
+    Foreign,
+}
+
+/// Running count of validated keys per tier
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TierCounts {
+    pub free: usize,
+    pub paid: usize,
+    pub invalid: usize,
+    pub suspended: usize,
+    pub rate_limited: usize,
+    pub errored: usize,
+    pub foreign: usize,
+}
+
+impl TierCounts {
+    pub fn record(&mut self, tier: &KeyTier) {
+        match tier {
+            KeyTier::Free => self.free += 1,
+            KeyTier::Paid => self.paid += 1,
+            KeyTier::Invalid => self.invalid += 1,
+            KeyTier::Suspended => self.suspended += 1,
+            KeyTier::RateLimited => self.rate_limited += 1,
+            KeyTier::Errored => self.errored += 1,
+            KeyTier::Foreign => self.foreign += 1,
+        }
+    }
+
+    /// Number of keys across all tiers
+    pub fn total(&self) -> usize {
+        self.free
+            + self.paid
+            + self.invalid
+            + self.suspended
+            + self.rate_limited
+            + self.errored
+            + self.foreign
+    }
+}
+
+impl fmt::Display for TierCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Free={} Paid={} Invalid={} Suspended={} RateLimited={} Errored={} Foreign={}",
+            self.free,
+            self.paid,
+            self.invalid,
+            self.suspended,
+            self.rate_limited,
+            self.errored,
+            self.foreign
+        )
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ValidatedKey {
     pub key: GeminiKey,
     pub tier: KeyTier,
+    // Time spent validating the key, in milliseconds
+    pub latency_ms: u64,
+    // Error that caused the key to be classified as invalid or errored
+    pub error: Option<String>,
+    // Google's machine-readable reason for the failure, e.g. API_KEY_INVALID
+    pub reason: Option<String>,
+    // Kind of failure, e.g. timeout or server_error, see `ValidatorError::category`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_category: Option<String>,
+    // Whether the failure was a timeout, also when it is categorized as a proxy error
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub timed_out: bool,
+    // Quota exhausted by a rate-limited key, from the QuotaFailure detail of the 429.
+    // The quota id tells per-minute limits from per-day ones.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota_metric: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota_id: Option<String>,
+    // Google Cloud project the key belongs to, when a response named it. Keys of one
+    // project share its quota.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_hint: Option<String>,
+    // Number of retries consumed while validating the key
+    pub retries: usize,
+    // HTTP requests sent to validate the key, retries and probes included
+    #[serde(skip)]
+    pub requests: usize,
+    // Whether the input line was rejected by the format check without a request
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub rejected: bool,
+    // Whether each of the configured extra models accepted a generateContent call
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub models: BTreeMap<String, bool>,
 }
 
 impl ValidatedKey {
@@ -49,6 +277,18 @@ impl ValidatedKey {
         Self {
             key,
             tier: KeyTier::Free,
+            latency_ms: 0,
+            error: None,
+            reason: None,
+            error_category: None,
+            timed_out: false,
+            quota_metric: None,
+            quota_id: None,
+            project_hint: None,
+            retries: 0,
+            requests: 0,
+            rejected: false,
+            models: BTreeMap::new(),
         }
     }
 
@@ -56,4 +296,51 @@ impl ValidatedKey {
         self.tier = KeyTier::Paid;
         self
     }
+
+    pub fn with_tier(mut self, tier: KeyTier) -> Self {
+        self.tier = tier;
+        self
+    }
+
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency_ms = latency.as_millis() as u64;
+        self
+    }
+
+    pub fn with_models(mut self, models: BTreeMap<String, bool>) -> Self {
+        self.models = models;
+        self
+    }
+
+    pub fn with_project_hint(mut self, project_hint: Option<String>) -> Self {
+        self.project_hint = project_hint;
+        self
+    }
+
+    pub fn with_rejected(mut self) -> Self {
+        self.rejected = true;
+        self
+    }
+
+    pub fn with_attempts(mut self, attempts: Attempts) -> Self {
+        self.retries = attempts.retries;
+        self.requests = attempts.sent;
+        self
+    }
+
+    pub fn with_error(mut self, error: &ValidatorError) -> Self {
+        self.error = Some(match error.root_cause() {
+            Some(cause) => format!("{error}: {cause}"),
+            None => error.to_string(),
+        });
+        self.reason = error.google_reason();
+        self.error_category = Some(error.category().to_string());
+        self.timed_out = error.is_timeout();
+        self.project_hint = error.project_hint();
+        if let Some(violation) = error.quota_violation() {
+            self.quota_metric = violation.metric;
+            self.quota_id = violation.quota_id;
+        }
+        self
+    }
 }
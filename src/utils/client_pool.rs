@@ -0,0 +1,154 @@
+use crate::error::ValidatorError;
+use futures::future::select_all;
+use reqwest::Client;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tracing::{info, warn};
+
+// Consecutive connection errors before a client is taken out of rotation
+const FAILURE_THRESHOLD: usize = 3;
+// How long a failing client stays out of rotation
+const COOLDOWN: Duration = Duration::from_secs(30);
+
+struct PooledClient {
+    client: Client,
+    consecutive_failures: AtomicUsize,
+    benched_until: Mutex<Option<Instant>>,
+    // Caps the requests in flight through this client, when it has its own limit
+    limit: Option<Semaphore>,
+}
+
+/// Round-robin pool of clients, one per proxy.
+///
+/// Clients that keep failing to connect are skipped until their cooldown expires, and
+/// clients at their own concurrency limit are passed over while others have room.
+pub struct ClientPool {
+    clients: Vec<PooledClient>,
+    cursor: AtomicUsize,
+}
+
+impl ClientPool {
+    /// Pools the clients, each with an optional limit of requests in flight through it
+    pub fn new(clients: Vec<(Client, Option<usize>)>) -> Self {
+        assert!(!clients.is_empty(), "client pool needs at least one client");
+        Self {
+            clients: clients
+                .into_iter()
+                .map(|(client, limit)| PooledClient {
+                    client,
+                    consecutive_failures: AtomicUsize::new(0),
+                    benched_until: Mutex::new(None),
+                    limit: limit.map(Semaphore::new),
+                })
+                .collect(),
+            cursor: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the index and client to use for the next request, along with the permit
+    /// held for the request's duration when the client has its own concurrency limit.
+    ///
+    /// Falls back to the next client in order when every client is benched, and waits for
+    /// whichever available client frees up first when all of them are at their limit.
+    pub async fn next(&self) -> (usize, Client, Option<SemaphorePermit<'_>>) {
+        let start = self.cursor.fetch_add(1, Ordering::Relaxed);
+        let now = Instant::now();
+
+        let mut saturated = Vec::new();
+        for index in (0..self.clients.len()).map(|offset| (start + offset) % self.clients.len()) {
+            if !self.is_available(index, now) {
+                continue;
+            }
+            let pooled = &self.clients[index];
+            match &pooled.limit {
+                None => return (index, pooled.client.clone(), None),
+                Some(limit) => match limit.try_acquire() {
+                    Ok(permit) => return (index, pooled.client.clone(), Some(permit)),
+                    Err(_) => saturated.push((index, limit)),
+                },
+            }
+        }
+
+        if !saturated.is_empty() {
+            let (indices, limits): (Vec<usize>, Vec<&Semaphore>) = saturated.into_iter().unzip();
+            let acquires = limits.into_iter().map(|limit| Box::pin(limit.acquire()));
+            let (permit, position, _) = select_all(acquires).await;
+            let index = indices[position];
+            let permit = permit.expect("proxy concurrency semaphore is never closed");
+            return (index, self.clients[index].client.clone(), Some(permit));
+        }
+
+        let index = start % self.clients.len();
+        let pooled = &self.clients[index];
+        let permit = match &pooled.limit {
+            Some(limit) => Some(
+                limit
+                    .acquire()
+                    .await
+                    .expect("proxy concurrency semaphore is never closed"),
+            ),
+            None => None,
+        };
+        (index, pooled.client.clone(), permit)
+    }
+
+    /// Records the outcome of a request made with the client at `index`
+    pub fn report(&self, index: usize, error: Option<&ValidatorError>) {
+        let pooled = &self.clients[index];
+
+        if !error.is_some_and(ValidatorError::is_connection_error) {
+            pooled.consecutive_failures.store(0, Ordering::Relaxed);
+            return;
+        }
+
+        let failures = pooled.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures >= FAILURE_THRESHOLD && self.clients.len() > 1 {
+            pooled.consecutive_failures.store(0, Ordering::Relaxed);
+            *pooled.benched_until.lock().unwrap() = Some(Instant::now() + COOLDOWN);
+            warn!(
+                "Proxy #{} failed {} times in a row, out of rotation for {}s",
+                index,
+                failures,
+                COOLDOWN.as_secs()
+            );
+        }
+    }
+
+    fn is_available(&self, index: usize, now: Instant) -> bool {
+        let mut benched_until = self.clients[index].benched_until.lock().unwrap();
+        match *benched_until {
+            Some(until) if until > now => false,
+            Some(_) => {
+                *benched_until = None;
+                info!("Proxy #{} back in rotation", index);
+                true
+            }
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn waits_for_whichever_saturated_client_frees_first() {
+        let pool = ClientPool::new(vec![(Client::new(), Some(1)), (Client::new(), Some(1))]);
+        let (first, _, first_permit) = pool.next().await;
+        let (second, _, second_permit) = pool.next().await;
+        assert_ne!(first, second);
+
+        // The cursor points at `first` again, yet `second` is the one released
+        let waiting = pool.next();
+        tokio::pin!(waiting);
+        assert!(futures::poll!(waiting.as_mut()).is_pending());
+        drop(second_permit);
+        let (index, _, permit) = waiting.await;
+        assert_eq!(index, second);
+        assert!(permit.is_some());
+        drop(first_permit);
+    }
+}
@@ -0,0 +1,38 @@
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static COLOR_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// ANSI foreground colors used in the terminal output
+#[derive(Debug, Clone, Copy)]
+pub enum Color {
+    Green,
+    Red,
+    Yellow,
+    Cyan,
+}
+
+impl Color {
+    fn code(self) -> &'static str {
+        match self {
+            Color::Green => "32",
+            Color::Red => "31",
+            Color::Yellow => "33",
+            Color::Cyan => "36",
+        }
+    }
+}
+
+/// Turns colored output on or off for every later `paint` call
+pub fn set_color_enabled(enabled: bool) {
+    COLOR_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Wraps the text in the color's escape codes when colors are enabled
+pub fn paint(text: impl Display, color: Color) -> String {
+    if COLOR_ENABLED.load(Ordering::Relaxed) {
+        format!("\x1b[{}m{text}\x1b[0m", color.code())
+    } else {
+        text.to_string()
+    }
+}
@@ -0,0 +1,243 @@
+//! Minimal HTTP/1.1 server for tests, answering every request with a canned response
+//! and recording what it received.
+
+use crate::config::KeyCheckerConfig;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use url::Url;
+
+/// Response sent for one request
+#[derive(Debug, Clone)]
+pub(crate) struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    // Time the server waits before answering
+    pub delay: Duration,
+}
+
+impl MockResponse {
+    pub fn new(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.into(),
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+/// Request as the server read it off the wire
+#[derive(Debug, Clone)]
+pub(crate) struct RecordedRequest {
+    pub method: String,
+    // Path and query, e.g. `/v1beta/models?key=...`
+    pub target: String,
+    // Protocol of the request line, `HTTP/2.0` for an HTTP/2 connection preface
+    pub version: String,
+    // Header names are lowercase
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl RecordedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+type Responder = dyn Fn(&RecordedRequest) -> MockResponse + Send + Sync;
+
+/// Server bound to a free local port until dropped
+pub(crate) struct MockServer {
+    url: Url,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    peak_in_flight: Arc<AtomicUsize>,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    pub async fn start(
+        responder: impl Fn(&RecordedRequest) -> MockResponse + Send + Sync + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let peak_in_flight = Arc::new(AtomicUsize::new(0));
+        let state = Arc::new(State {
+            responder: Box::new(responder),
+            requests: requests.clone(),
+            in_flight: AtomicUsize::new(0),
+            peak_in_flight: peak_in_flight.clone(),
+        });
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, state.clone()));
+            }
+        });
+        Self {
+            url,
+            requests,
+            peak_in_flight,
+            task,
+        }
+    }
+
+    /// Answers with `responses` in turn, repeating the last one once they run out
+    pub async fn sequence(responses: Vec<MockResponse>) -> Self {
+        let next = AtomicUsize::new(0);
+        Self::start(move |_| {
+            let index = next.fetch_add(1, Ordering::SeqCst);
+            responses[index.min(responses.len() - 1)].clone()
+        })
+        .await
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Most requests that were being answered at the same time
+    pub fn peak_in_flight(&self) -> usize {
+        self.peak_in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Configuration sending every request to this server without retries, writing
+    /// its outputs and backup to `output_dir` and keeping no checkpoint
+    pub fn config(&self, output_dir: &Path) -> KeyCheckerConfig {
+        KeyCheckerConfig {
+            api_host: self.url.clone(),
+            output_dir: output_dir.to_path_buf(),
+            backup_path: output_dir.join("backup_keys.txt"),
+            checkpoint_path: None,
+            max_retries: 0,
+            retry_base_delay_ms: 1,
+            skip_preflight: true,
+            no_progress: true,
+            ..KeyCheckerConfig::default()
+        }
+    }
+}
+
+/// Empty directory under the system temp dir, unique to the label and the process
+pub(crate) fn temp_dir(label: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("keychecker_{label}_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+struct State {
+    responder: Box<Responder>,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    in_flight: AtomicUsize,
+    peak_in_flight: Arc<AtomicUsize>,
+}
+
+// Answers the requests of one keep-alive connection until the client closes it
+async fn serve(mut stream: TcpStream, state: Arc<State>) {
+    let mut buffer = Vec::new();
+    while let Some(request) = read_request(&mut stream, &mut buffer).await {
+        let in_flight = state.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        state.peak_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+        let response = (state.responder)(&request);
+        let preface = request.method == "PRI";
+        state.requests.lock().unwrap().push(request);
+
+        tokio::time::sleep(response.delay).await;
+        let mut head = format!(
+            "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\n",
+            response.status,
+            response.body.len()
+        );
+        for (name, value) in &response.headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        head.push_str("\r\n");
+        let written = stream.write_all(head.as_bytes()).await.is_ok()
+            && stream.write_all(response.body.as_bytes()).await.is_ok();
+        state.in_flight.fetch_sub(1, Ordering::SeqCst);
+        // An HTTP/2 client cannot read the HTTP/1.1 answer
+        if !written || preface {
+            return;
+        }
+    }
+}
+
+async fn read_request(stream: &mut TcpStream, buffer: &mut Vec<u8>) -> Option<RecordedRequest> {
+    let head_end = loop {
+        if let Some(position) = buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position;
+        }
+        let mut chunk = [0u8; 4096];
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            return None;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    };
+    let head = String::from_utf8_lossy(&buffer[..head_end]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let (method, target, version) = (
+        request_line.next()?.to_string(),
+        request_line.next()?.to_string(),
+        request_line.next()?.to_string(),
+    );
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect();
+    let length = headers
+        .iter()
+        .find(|(name, _)| name == "content-length")
+        .and_then(|(_, value)| value.parse().ok())
+        .unwrap_or(0);
+
+    buffer.drain(..head_end + 4);
+    while buffer.len() < length {
+        let mut chunk = [0u8; 4096];
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            return None;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+    let body = buffer.drain(..length).collect();
+    Some(RecordedRequest {
+        method,
+        target,
+        version,
+        headers,
+        body,
+    })
+}
@@ -1,5 +1,16 @@
+pub mod client_pool;
+pub mod color;
 pub mod http;
+#[cfg(test)]
+pub(crate) mod mock_server;
+pub mod preflight;
 pub mod writer;
 
-pub use http::{client_builder, send_request};
-pub use writer::write_key_into_file;
+pub use client_pool::ClientPool;
+pub use color::{Color, paint, set_color_enabled};
+pub use http::{
+    Attempts, Credential, RetryPolicy, client_builder, direct_client_builder, get_request,
+    json_body, proxy_clients_builder, send_request,
+};
+pub use preflight::{check_api_host, check_proxies, proxy_label};
+pub use writer::{ResultSink, SinkWriter, TierWriters, sort_by_key};
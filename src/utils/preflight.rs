@@ -0,0 +1,104 @@
+use crate::error::ValidatorError;
+use futures::future::join_all;
+use reqwest::{Client, StatusCode};
+use tracing::{info, warn};
+use url::Url;
+
+/// Sends one request without a key to `url` so DNS, TLS and the proxy, if `proxied`, are
+/// known to work before any key is spent. The API answers it with a 4xx, and any HTTP
+/// response counts as reachable. On failure the error names the stage that failed.
+pub async fn check_api_host(
+    client: &Client,
+    url: &Url,
+    proxied: bool,
+) -> Result<(), ValidatorError> {
+    let unreachable = |diagnosis: String| {
+        ValidatorError::ApiHostUnreachable(format!(
+            "{diagnosis} (pass --skip-preflight to start anyway)"
+        ))
+    };
+    match client.get(url.clone()).send().await {
+        Ok(response) if response.status() == StatusCode::PROXY_AUTHENTICATION_REQUIRED => Err(
+            unreachable("the proxy rejected the credentials (HTTP 407)".to_string()),
+        ),
+        Ok(response) => {
+            info!(
+                "API host {} reachable (HTTP {})",
+                url.host_str().unwrap_or_default(),
+                response.status().as_u16()
+            );
+            Ok(())
+        }
+        Err(e) => Err(unreachable(diagnose(&e.into(), url, proxied))),
+    }
+}
+
+// Names the stage a request failed at, DNS, TLS, the proxy or a timeout, followed by
+// the innermost cause
+fn diagnose(error: &ValidatorError, url: &Url, proxied: bool) -> String {
+    let cause = error.root_cause().unwrap_or_else(|| error.to_string());
+    let host = url.host_str().unwrap_or_default();
+    match error.category() {
+        "dns" => format!("DNS lookup of {host} failed: {cause}"),
+        "tls" => format!(
+            "TLS handshake with {host} failed, check ca_cert_path for TLS inspection proxies: {cause}"
+        ),
+        "connect_refused" | "connect" if proxied => {
+            format!("connecting through the proxy to {host} failed: {cause}")
+        }
+        "proxy" => format!("connecting through the proxy to {host} failed: {cause}"),
+        "timeout" => format!(
+            "no response from {host} within the timeout, check the network or raise timeout_sec"
+        ),
+        "connect_refused" | "connect" => format!("cannot connect to {host}: {cause}"),
+        _ => format!("request to {url} failed: {cause}"),
+    }
+}
+
+/// Sends one request through each proxy client and reports which of them can reach `url`.
+/// Any HTTP response from the API host counts as reachable, since no key is sent.
+///
+/// Returns the labels of the proxies that failed the check.
+pub async fn check_proxies(proxies: &[(String, Client)], url: &Url) -> Vec<String> {
+    let checks = proxies.iter().map(|(label, client)| async move {
+        let result = client.get(url.clone()).send().await;
+        (label, result)
+    });
+
+    let mut unhealthy = Vec::new();
+    for (label, result) in join_all(checks).await {
+        match result {
+            // An HTTP proxy answering for the upstream with its own auth challenge
+            Ok(response) if response.status() == StatusCode::PROXY_AUTHENTICATION_REQUIRED => {
+                warn!("Proxy {} rejected the credentials (HTTP 407)", label);
+                unhealthy.push(label.clone());
+            }
+            Ok(response) => {
+                info!(
+                    "Proxy {} reachable (HTTP {})",
+                    label,
+                    response.status().as_u16()
+                );
+            }
+            Err(e) => {
+                warn!(
+                    "Proxy {} cannot reach {}: {}",
+                    label,
+                    url,
+                    ValidatorError::from(e.without_url())
+                );
+                unhealthy.push(label.clone());
+            }
+        }
+    }
+    unhealthy
+}
+
+/// Label identifying a proxy in logs without its credentials
+pub fn proxy_label(proxy: &Url) -> String {
+    match (proxy.host_str(), proxy.port_or_known_default()) {
+        (Some(host), Some(port)) => format!("{}://{}:{}", proxy.scheme(), host, port),
+        (Some(host), None) => format!("{}://{}", proxy.scheme(), host),
+        _ => proxy.scheme().to_string(),
+    }
+}
@@ -1,16 +1,533 @@
+use crate::adapters::output::{
+    OPENAI_PROXY_EXPORT_PATH, decode_output, is_gzip, write_validated_keys_json,
+};
+use crate::config::{KeyCheckerConfig, OutputFormat};
 use crate::error::ValidatorError;
-use crate::types::ValidatedKey;
-use tokio::io::{AsyncWrite, AsyncWriteExt};
-
-pub async fn write_key_into_file<W>(
-    writer: &mut W,
-    validated_key: &ValidatedKey,
-) -> Result<(), ValidatorError>
-where
-    W: AsyncWrite + Unpin,
-{
-    writer
-        .write_all(format!("{}\n", validated_key.key.as_ref()).as_bytes())
-        .await?;
-    Ok(())
+use crate::types::{KeyTier, ValidatedKey};
+use crate::validation::validation_service::{ERRORS_PATH, JSON_SUMMARY_PATH, MANIFEST_PATH};
+use async_compression::tokio::write::GzipEncoder;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::{AsyncWrite, AsyncWriteExt, BufWriter, Stdout};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{self, MissedTickBehavior};
+use tracing::error;
+
+// Plain file, or a gzip encoder over one for `.gz` paths
+type TierOutput = Box<dyn AsyncWrite + Unpin + Send>;
+
+/// Key-per-line output file of one tier
+pub struct TierFile {
+    writer: BufWriter<TierOutput>,
+    // Keys already in the file when appending, which are not written again
+    existing: HashSet<String>,
+    // Lines held back until `finish` when the output is sorted
+    pending: Option<Vec<String>>,
+}
+
+impl TierFile {
+    // `separator` ends the key on lines of an existing file read when appending
+    async fn create(
+        path: PathBuf,
+        append: bool,
+        sort: bool,
+        separator: &str,
+    ) -> Result<Self, ValidatorError> {
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent).await?;
+        }
+        let (file, existing) = if append {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .await?;
+            (file, existing_keys(&path, separator).await?)
+        } else {
+            (File::create(&path).await?, HashSet::new())
+        };
+        let output: TierOutput = if is_gzip(&path) {
+            Box::new(GzipEncoder::new(file))
+        } else {
+            Box::new(file)
+        };
+        Ok(Self {
+            writer: BufWriter::new(output),
+            existing,
+            pending: sort.then(Vec::new),
+        })
+    }
+
+    // Writes the line now, or holds it back when the output is sorted
+    async fn write_line(&mut self, line: String) -> Result<(), ValidatorError> {
+        match self.pending.as_mut() {
+            Some(pending) => pending.push(line),
+            None => self.writer.write_all(line.as_bytes()).await?,
+        }
+        Ok(())
+    }
+
+    // Writes the held back lines in key order, then completes the file
+    async fn finish(&mut self) -> Result<(), ValidatorError> {
+        if let Some(pending) = self.pending.as_mut() {
+            pending.sort_unstable();
+            for line in pending.drain(..) {
+                self.writer.write_all(line.as_bytes()).await?;
+            }
+        }
+        self.writer.shutdown().await?;
+        Ok(())
+    }
+}
+
+// Keys already in an output file, each ending at `separator`. Tier file lines may carry
+// a ` # <ms>ms` latency suffix, single file lines the tier after the delimiter.
+async fn existing_keys(path: &Path, separator: &str) -> Result<HashSet<String>, ValidatorError> {
+    let existing = match fs::read(path).await {
+        Ok(content) => decode_output(path, &content)?
+            .lines()
+            .filter_map(|line| line.split(separator).next())
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(str::to_string)
+            .collect(),
+        Err(e) if e.kind() == ErrorKind::NotFound => HashSet::new(),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(existing)
+}
+
+/// Buffered writers for the key-per-line output file of each tier
+pub struct TierWriters {
+    free: TierFile,
+    paid: TierFile,
+    invalid: TierFile,
+    suspended: TierFile,
+    rate_limited: TierFile,
+    errored: TierFile,
+    foreign: TierFile,
+    // Append ` # <ms>ms` after each key
+    show_latency: bool,
+}
+
+impl TierWriters {
+    // Fixed output filenames for each tier
+    const FREE_KEYS_PATH: &str = "freekey.txt";
+    const PAID_KEYS_PATH: &str = "paidkey.txt";
+    const INVALID_KEYS_PATH: &str = "invalidkey.txt";
+    const SUSPENDED_KEYS_PATH: &str = "suspendedkey.txt";
+    const RATE_LIMITED_KEYS_PATH: &str = "ratelimitedkey.txt";
+    const ERRORED_KEYS_PATH: &str = "errorkey.txt";
+    const FOREIGN_KEYS_PATH: &str = "foreignkey.txt";
+    // Starts the latency suffix after a key
+    const LATENCY_SEPARATOR: &str = " #";
+
+    /// Opens the tier files inside `output_dir`, or at the configured per-tier paths,
+    /// truncating them unless `append` is set, in which case keys already present in a
+    /// file are skipped. `compress` writes `.gz` files. With `sort_output` every key is
+    /// held in memory and written in order by `finish`.
+    pub async fn create(
+        output_dir: &Path,
+        config: &KeyCheckerConfig,
+    ) -> Result<Self, ValidatorError> {
+        let open = |custom: &Option<PathBuf>, default| {
+            let path = match custom {
+                Some(path) if config.compress && !is_gzip(path) => {
+                    let mut path = path.clone().into_os_string();
+                    path.push(".gz");
+                    PathBuf::from(path)
+                }
+                Some(path) => path.clone(),
+                None => output_path(output_dir, default, config.compress),
+            };
+            TierFile::create(
+                path,
+                config.append,
+                config.sort_output,
+                Self::LATENCY_SEPARATOR,
+            )
+        };
+        Ok(Self {
+            free: open(&config.free_path, Self::FREE_KEYS_PATH).await?,
+            paid: open(&config.paid_path, Self::PAID_KEYS_PATH).await?,
+            invalid: open(&config.invalid_path, Self::INVALID_KEYS_PATH).await?,
+            suspended: open(&config.suspended_path, Self::SUSPENDED_KEYS_PATH).await?,
+            rate_limited: open(&config.rate_limited_path, Self::RATE_LIMITED_KEYS_PATH).await?,
+            errored: open(&config.errored_path, Self::ERRORED_KEYS_PATH).await?,
+            foreign: open(&config.foreign_path, Self::FOREIGN_KEYS_PATH).await?,
+            show_latency: config.show_latency,
+        })
+    }
+
+    /// Append the key to the file matching its tier
+    pub async fn write(&mut self, validated_key: &ValidatedKey) -> Result<(), ValidatorError> {
+        let tier_file = match validated_key.tier {
+            KeyTier::Free => &mut self.free,
+            KeyTier::Paid => &mut self.paid,
+            KeyTier::Invalid => &mut self.invalid,
+            KeyTier::Suspended => &mut self.suspended,
+            KeyTier::RateLimited => &mut self.rate_limited,
+            KeyTier::Errored => &mut self.errored,
+            KeyTier::Foreign => &mut self.foreign,
+        };
+        if tier_file.existing.contains(validated_key.key.as_ref()) {
+            return Ok(());
+        }
+        let line = if self.show_latency {
+            format!(
+                "{} # {}ms\n",
+                validated_key.key.as_ref(),
+                validated_key.latency_ms
+            )
+        } else {
+            format!("{}\n", validated_key.key.as_ref())
+        };
+        tier_file.write_line(line).await
+    }
+
+    pub async fn flush(&mut self) -> Result<(), ValidatorError> {
+        self.free.writer.flush().await?;
+        self.paid.writer.flush().await?;
+        self.invalid.writer.flush().await?;
+        self.suspended.writer.flush().await?;
+        self.rate_limited.writer.flush().await?;
+        self.errored.writer.flush().await?;
+        self.foreign.writer.flush().await?;
+        Ok(())
+    }
+
+    /// Writes held back keys of sorted outputs, flushes every file and completes the
+    /// gzip streams of compressed ones
+    pub async fn finish(&mut self) -> Result<(), ValidatorError> {
+        self.free.finish().await?;
+        self.paid.finish().await?;
+        self.invalid.finish().await?;
+        self.suspended.finish().await?;
+        self.rate_limited.finish().await?;
+        self.errored.finish().await?;
+        self.foreign.finish().await?;
+        Ok(())
+    }
+}
+
+/// Whether a run writes a text or JSON file of this name into its output directory, the
+/// kinds of file a directory input is searched for
+pub fn is_output_file_name(name: &OsStr) -> bool {
+    [
+        TierWriters::FREE_KEYS_PATH,
+        TierWriters::PAID_KEYS_PATH,
+        TierWriters::INVALID_KEYS_PATH,
+        TierWriters::SUSPENDED_KEYS_PATH,
+        TierWriters::RATE_LIMITED_KEYS_PATH,
+        TierWriters::ERRORED_KEYS_PATH,
+        TierWriters::FOREIGN_KEYS_PATH,
+        ResultSink::JSON_RESULTS_PATH,
+        ResultSink::SINGLE_FILE_PATH,
+        JSON_SUMMARY_PATH,
+        ERRORS_PATH,
+        MANIFEST_PATH,
+        OPENAI_PROXY_EXPORT_PATH,
+    ]
+    .iter()
+    .any(|output| name == *output)
+}
+
+// Path of an output file inside the output directory, with `.gz` added when compressing
+fn output_path(output_dir: &Path, filename: &str, compress: bool) -> PathBuf {
+    if compress {
+        output_dir.join(format!("{filename}.gz"))
+    } else {
+        output_dir.join(filename)
+    }
+}
+
+/// Destination for validation results in the configured output format
+pub enum ResultSink {
+    Text(Box<TierWriters>),
+    // JSON is written as a single array, so results are buffered until the run ends
+    Json {
+        validated_keys: Vec<ValidatedKey>,
+        path: PathBuf,
+        // Merge into the existing results file instead of replacing it
+        append: bool,
+        // Order the results by key rather than by completion
+        sort: bool,
+    },
+    // Results streamed to stdout, one line per key, while logs stay on stderr
+    Ndjson(BufWriter<Stdout>),
+    // Text output merged into one file, each key annotated with its tier
+    SingleFile {
+        file: Box<TierFile>,
+        delimiter: String,
+        show_latency: bool,
+    },
+}
+
+/// Line written per key in NDJSON output. The key is masked unless full-key logging is on.
+#[derive(Serialize)]
+struct NdjsonLine<'a> {
+    key: String,
+    tier: &'a KeyTier,
+    latency_ms: u64,
+    error: Option<&'a str>,
+}
+
+impl ResultSink {
+    // Output file used when results are written as JSON
+    const JSON_RESULTS_PATH: &str = "results.json";
+    // Output file used when every tier is written to one file
+    const SINGLE_FILE_PATH: &str = "allkeys.txt";
+
+    /// Opens the configured outputs inside `output_dir`
+    pub async fn create(
+        config: &KeyCheckerConfig,
+        output_dir: &Path,
+    ) -> Result<Self, ValidatorError> {
+        Ok(match config.output_format {
+            OutputFormat::Text if config.single_file => {
+                let delimiter = config.single_file_delimiter.replace("\\t", "\t");
+                let path = output_path(output_dir, Self::SINGLE_FILE_PATH, config.compress);
+                Self::SingleFile {
+                    file: Box::new(
+                        TierFile::create(path, config.append, config.sort_output, &delimiter)
+                            .await?,
+                    ),
+                    delimiter,
+                    show_latency: config.show_latency,
+                }
+            }
+            OutputFormat::Text => {
+                Self::Text(Box::new(TierWriters::create(output_dir, config).await?))
+            }
+            OutputFormat::Json => Self::Json {
+                validated_keys: Vec::new(),
+                path: output_path(output_dir, Self::JSON_RESULTS_PATH, config.compress),
+                append: config.append,
+                sort: config.sort_output,
+            },
+            OutputFormat::Ndjson => Self::Ndjson(BufWriter::new(tokio::io::stdout())),
+        })
+    }
+
+    pub async fn write(&mut self, validated_key: &ValidatedKey) -> Result<(), ValidatorError> {
+        match self {
+            Self::Text(tier_writers) => tier_writers.write(validated_key).await,
+            Self::Json { validated_keys, .. } => {
+                validated_keys.push(validated_key.clone());
+                Ok(())
+            }
+            Self::Ndjson(stdout) => {
+                let line = NdjsonLine {
+                    key: validated_key.key.masked(),
+                    tier: &validated_key.tier,
+                    latency_ms: validated_key.latency_ms,
+                    error: validated_key.error.as_deref(),
+                };
+                let mut line = serde_json::to_vec(&line)?;
+                line.push(b'\n');
+                stdout.write_all(&line).await?;
+                // Flushed per key so downstream tools see each result immediately
+                stdout.flush().await?;
+                Ok(())
+            }
+            Self::SingleFile {
+                file,
+                delimiter,
+                show_latency,
+            } => {
+                let key = validated_key.key.as_ref();
+                if file.existing.contains(key) {
+                    return Ok(());
+                }
+                let mut line = format!("{key}{delimiter}{:?}", validated_key.tier);
+                if *show_latency {
+                    line.push_str(&format!("{delimiter}{}ms", validated_key.latency_ms));
+                }
+                line.push('\n');
+                file.write_line(line).await
+            }
+        }
+    }
+
+    /// Hands buffered lines to the files. JSON output and keys held back for sorting are
+    /// only written by `finish`.
+    pub async fn flush(&mut self) -> Result<(), ValidatorError> {
+        match self {
+            Self::Text(tier_writers) => tier_writers.flush().await,
+            Self::SingleFile { file, .. } => Ok(file.writer.flush().await?),
+            Self::Json { .. } | Self::Ndjson(_) => Ok(()),
+        }
+    }
+
+    /// Flushes buffered output, writing the JSON file if needed
+    pub async fn finish(self) -> Result<(), ValidatorError> {
+        match self {
+            Self::Text(mut tier_writers) => tier_writers.finish().await,
+            Self::Json {
+                mut validated_keys,
+                path,
+                append,
+                sort,
+            } => {
+                if sort {
+                    sort_by_key(&mut validated_keys);
+                }
+                write_validated_keys_json(&validated_keys, &path, append)
+            }
+            Self::Ndjson(mut stdout) => Ok(stdout.flush().await?),
+            Self::SingleFile { mut file, .. } => file.finish().await,
+        }
+    }
+}
+
+/// Handle to a task that owns a `ResultSink` and writes every result sent to it, so disk
+/// I/O runs beside the validation loop instead of holding up the collection of responses.
+/// Results are written in the order they are sent, and flushed at most `flush_interval`
+/// after being written, or right away when it is zero.
+pub struct SinkWriter {
+    sender: mpsc::Sender<ValidatedKey>,
+    task: JoinHandle<Result<(), ValidatorError>>,
+}
+
+impl SinkWriter {
+    // Results queued before senders wait for the writer to catch up
+    const QUEUE_CAPACITY: usize = 1024;
+
+    pub fn spawn(mut sink: ResultSink, flush_interval: Duration) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<ValidatedKey>(Self::QUEUE_CAPACITY);
+        let task = tokio::spawn(async move {
+            let flush_every_key = flush_interval.is_zero();
+            // Only polled while there is unflushed output, so never with `flush_every_key`
+            let mut ticker = time::interval(flush_interval.max(Duration::from_secs(1)));
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            let mut unflushed = false;
+            loop {
+                tokio::select! {
+                    received = receiver.recv() => {
+                        let Some(validated_key) = received else {
+                            break;
+                        };
+                        if let Err(e) = sink.write(&validated_key).await {
+                            error!("Failed to write {:?} key to file: {e}", validated_key.tier);
+                        }
+                        if flush_every_key {
+                            if let Err(e) = sink.flush().await {
+                                error!("Failed to flush output files: {e}");
+                            }
+                        } else if !unflushed {
+                            unflushed = true;
+                            ticker.reset();
+                        }
+                    }
+                    _ = ticker.tick(), if unflushed => {
+                        unflushed = false;
+                        if let Err(e) = sink.flush().await {
+                            error!("Failed to flush output files: {e}");
+                        }
+                    }
+                }
+            }
+            sink.finish().await
+        });
+        Self { sender, task }
+    }
+
+    /// Queues the key for writing, waiting while the queue is full
+    pub async fn write(&self, validated_key: ValidatedKey) -> Result<(), ValidatorError> {
+        self.sender
+            .send(validated_key)
+            .await
+            .map_err(|_| std::io::Error::other("the result writer stopped").into())
+    }
+
+    /// Writes every queued result, then flushes and completes the outputs
+    pub async fn finish(self) -> Result<(), ValidatorError> {
+        drop(self.sender);
+        self.task.await.map_err(std::io::Error::other)?
+    }
+}
+
+/// Orders validated keys by the key itself, for `sort_output`
+pub fn sort_by_key(validated_keys: &mut [ValidatedKey]) {
+    validated_keys.sort_unstable_by(|a, b| a.key.as_ref().cmp(b.key.as_ref()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::GeminiKey;
+    use crate::utils::mock_server::temp_dir;
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    const TIERS: [(KeyTier, &str); 7] = [
+        (KeyTier::Free, TierWriters::FREE_KEYS_PATH),
+        (KeyTier::Paid, TierWriters::PAID_KEYS_PATH),
+        (KeyTier::Invalid, TierWriters::INVALID_KEYS_PATH),
+        (KeyTier::Suspended, TierWriters::SUSPENDED_KEYS_PATH),
+        (KeyTier::RateLimited, TierWriters::RATE_LIMITED_KEYS_PATH),
+        (KeyTier::Errored, TierWriters::ERRORED_KEYS_PATH),
+        (KeyTier::Foreign, TierWriters::FOREIGN_KEYS_PATH),
+    ];
+
+    fn test_key(index: usize) -> String {
+        format!("AIzaSy{index:0>33}")
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn routes_concurrent_writes_to_their_tier_files() {
+        let output_dir = temp_dir("sink_writer");
+        let config = KeyCheckerConfig {
+            output_dir: output_dir.clone(),
+            ..KeyCheckerConfig::default()
+        };
+        let sink = ResultSink::create(&config, &output_dir).await.unwrap();
+        let writer = Arc::new(SinkWriter::spawn(sink, Duration::from_millis(10)));
+
+        // Tasks of every tier write at the same time, their keys interleaving in the queue
+        let tasks: Vec<_> = (0..TIERS.len())
+            .map(|tier_index| {
+                let writer = writer.clone();
+                tokio::spawn(async move {
+                    for index in (tier_index..700).step_by(TIERS.len()) {
+                        let key = GeminiKey::from_str(&test_key(index)).unwrap();
+                        let tier = TIERS[tier_index].0.clone();
+                        writer
+                            .write(ValidatedKey::new(key).with_tier(tier))
+                            .await
+                            .unwrap();
+                        tokio::task::yield_now().await;
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        let Ok(writer) = Arc::try_unwrap(writer) else {
+            panic!("a task still holds the writer");
+        };
+        writer.finish().await.unwrap();
+
+        for (tier_index, (tier, file)) in TIERS.iter().enumerate() {
+            let content = std::fs::read_to_string(output_dir.join(file)).unwrap();
+            let written: HashSet<&str> = content.lines().collect();
+            let expected: Vec<String> = (tier_index..700)
+                .step_by(TIERS.len())
+                .map(test_key)
+                .collect();
+            let expected: HashSet<&str> = expected.iter().map(String::as_str).collect();
+            assert_eq!(written, expected, "{tier:?} keys in {file}");
+            assert_eq!(content.lines().count(), 100, "{file}");
+        }
+        std::fs::remove_dir_all(&output_dir).unwrap();
+    }
 }
@@ -0,0 +1,104 @@
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::{Instant, sleep, sleep_until};
+use tracing::{info, warn};
+
+// How often tasks waiting on a half-open breaker check whether the probe finished
+const PROBE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Pauses all requests after a run of hard network failures, e.g. when the upstream or
+/// the proxy is down, instead of firing doomed requests for every remaining key.
+///
+/// Once the cool-down has passed a single probe request is let through; its success
+/// resumes normal operation, its failure pauses for another cool-down.
+pub struct CircuitBreaker {
+    // Consecutive failures that trip the breaker; 0 disables it
+    threshold: usize,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+enum BreakerState {
+    Closed { failures: usize },
+    Open { until: Instant },
+    // The probe request is in flight
+    HalfOpen,
+}
+
+/// Whether the request allowed through is the breaker's probe
+pub struct BreakerPass {
+    probe: bool,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: usize, cooldown: Duration) -> Self {
+        Self {
+            threshold,
+            cooldown,
+            state: Mutex::new(BreakerState::Closed { failures: 0 }),
+        }
+    }
+
+    /// Waits until a request may be sent
+    pub async fn pass(&self) -> BreakerPass {
+        loop {
+            let wait_until = {
+                let mut state = self.state.lock().unwrap();
+                match *state {
+                    BreakerState::Closed { .. } => return BreakerPass { probe: false },
+                    BreakerState::Open { until } if until <= Instant::now() => {
+                        *state = BreakerState::HalfOpen;
+                        info!("Circuit breaker cool-down over, sending a probe request");
+                        return BreakerPass { probe: true };
+                    }
+                    BreakerState::Open { until } => Some(until),
+                    BreakerState::HalfOpen => None,
+                }
+            };
+            match wait_until {
+                Some(until) => sleep_until(until).await,
+                None => sleep(PROBE_POLL_INTERVAL).await,
+            }
+        }
+    }
+
+    /// Records the outcome of a request let through by `pass`
+    pub fn record(&self, pass: BreakerPass, hard_failure: bool) {
+        if self.threshold == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        match (&mut *state, hard_failure) {
+            (BreakerState::Closed { failures }, true) => {
+                *failures += 1;
+                if *failures >= self.threshold {
+                    warn!(
+                        "Circuit breaker tripped after {} consecutive network failures, pausing requests for {}s",
+                        failures,
+                        self.cooldown.as_secs()
+                    );
+                    *state = BreakerState::Open {
+                        until: Instant::now() + self.cooldown,
+                    };
+                }
+            }
+            (BreakerState::Closed { failures }, false) => *failures = 0,
+            (BreakerState::HalfOpen, true) if pass.probe => {
+                warn!(
+                    "Circuit breaker probe failed, pausing requests for another {}s",
+                    self.cooldown.as_secs()
+                );
+                *state = BreakerState::Open {
+                    until: Instant::now() + self.cooldown,
+                };
+            }
+            // Any completed request shows the endpoint is reachable again
+            (BreakerState::HalfOpen | BreakerState::Open { .. }, false) => {
+                warn!("Circuit breaker reset, resuming requests");
+                *state = BreakerState::Closed { failures: 0 };
+            }
+            // Requests already in flight when the breaker tripped
+            (BreakerState::HalfOpen | BreakerState::Open { .. }, true) => {}
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::sync::Mutex;
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tracing::info;
+
+// Number of completed keys evaluated per adjustment window
+const WINDOW_SIZE: usize = 50;
+// Share of rate-limited results in a window that triggers a shrink
+const SHRINK_THRESHOLD: f64 = 0.2;
+
+/// Concurrency limit that halves when 429 responses spike and slowly ramps back up.
+pub struct AdaptiveConcurrency {
+    semaphore: Semaphore,
+    min: usize,
+    max: usize,
+    state: Mutex<ControllerState>,
+}
+
+#[derive(Default)]
+struct ControllerState {
+    current: usize,
+    window_total: usize,
+    window_rate_limited: usize,
+    // Permits still held by in-flight tasks that must be dropped when released
+    pending_forget: usize,
+}
+
+/// Slot held for the duration of one key validation
+pub struct ConcurrencyPermit<'a> {
+    permit: Option<SemaphorePermit<'a>>,
+    controller: &'a AdaptiveConcurrency,
+}
+
+impl Drop for ConcurrencyPermit<'_> {
+    fn drop(&mut self) {
+        let mut state = self.controller.state.lock().unwrap();
+        if state.pending_forget > 0 {
+            state.pending_forget -= 1;
+            if let Some(permit) = self.permit.take() {
+                permit.forget();
+            }
+        }
+    }
+}
+
+impl AdaptiveConcurrency {
+    pub fn new(min: usize, max: usize) -> Self {
+        let min = min.clamp(1, max.max(1));
+        Self {
+            semaphore: Semaphore::new(max),
+            min,
+            max,
+            state: Mutex::new(ControllerState {
+                current: max,
+                ..Default::default()
+            }),
+        }
+    }
+
+    pub async fn acquire(&self) -> ConcurrencyPermit<'_> {
+        let permit = self
+            .semaphore
+            .acquire()
+            .await
+            .expect("concurrency semaphore is never closed");
+        ConcurrencyPermit {
+            permit: Some(permit),
+            controller: self,
+        }
+    }
+
+    /// Records a completed key and adjusts the limit once a full window has been observed
+    pub fn record(&self, rate_limited: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.window_total += 1;
+        if rate_limited {
+            state.window_rate_limited += 1;
+        }
+        if state.window_total < WINDOW_SIZE {
+            return;
+        }
+
+        let ratio = state.window_rate_limited as f64 / state.window_total as f64;
+        state.window_total = 0;
+        state.window_rate_limited = 0;
+
+        let current = state.current;
+        let target = if ratio > SHRINK_THRESHOLD {
+            (current / 2).max(self.min)
+        } else if ratio == 0.0 {
+            (current + (current / 10).max(1)).min(self.max)
+        } else {
+            current
+        };
+
+        if target < current {
+            let shrink_by = current - target;
+            let forgotten = self.semaphore.forget_permits(shrink_by);
+            state.pending_forget += shrink_by - forgotten;
+        } else if target > current {
+            let mut grow_by = target - current;
+            let repaid = grow_by.min(state.pending_forget);
+            state.pending_forget -= repaid;
+            grow_by -= repaid;
+            self.semaphore.add_permits(grow_by);
+        } else {
+            return;
+        }
+
+        state.current = target;
+        info!(
+            "Adjusted concurrency {} -> {} ({:.0}% rate limited in last {} keys)",
+            current,
+            target,
+            ratio * 100.0,
+            WINDOW_SIZE
+        );
+    }
+}
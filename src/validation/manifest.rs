@@ -0,0 +1,81 @@
+use crate::adapters::input::{RunFiles, is_stdin_path, resolve_input_files};
+use crate::adapters::output::rfc3339;
+use crate::config::KeyCheckerConfig;
+use crate::error::ValidatorError;
+use crate::types::TierCounts;
+use crate::validation::ValidationSummary;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// Self-describing record of a run for auditing: when it ran, with which version and
+/// settings, over which input and with what result
+#[derive(Debug, Clone, Serialize)]
+pub struct RunManifest {
+    pub tool: &'static str,
+    pub version: &'static str,
+    // UTC start and end of the run in RFC 3339, e.g. 2025-08-11T01:55:12Z
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    pub duration_ms: u64,
+    // Whether the run was stopped early by Ctrl-C
+    pub interrupted: bool,
+    pub total_keys: usize,
+    pub counts: TierCounts,
+    // Every input file, hashed when the manifest is built
+    pub inputs: Vec<InputDigest>,
+    // Effective configuration, with secrets masked as in `--print-config`
+    pub config: KeyCheckerConfig,
+}
+
+/// Size and SHA-256 of an input file, both unset for stdin
+#[derive(Debug, Clone, Serialize)]
+pub struct InputDigest {
+    pub path: PathBuf,
+    pub bytes: Option<u64>,
+    pub sha256: Option<String>,
+}
+
+impl RunManifest {
+    /// Describes the run that produced `summary` with `config`, reading every input file
+    /// to hash it
+    pub fn new(
+        summary: &ValidationSummary,
+        config: &KeyCheckerConfig,
+    ) -> Result<Self, ValidatorError> {
+        let inputs = resolve_input_files(&config.input_path, &RunFiles::from_config(config))?
+            .iter()
+            .map(|path| digest(path))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            tool: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            started_at: summary.started_at.map(rfc3339),
+            finished_at: summary.finished_at.map(rfc3339),
+            duration_ms: summary.duration.as_millis() as u64,
+            interrupted: summary.interrupted,
+            total_keys: summary.total_keys,
+            counts: summary.counts.clone(),
+            inputs,
+            config: config.redacted(),
+        })
+    }
+}
+
+fn digest(path: &Path) -> Result<InputDigest, ValidatorError> {
+    if is_stdin_path(path) {
+        return Ok(InputDigest {
+            path: path.to_path_buf(),
+            bytes: None,
+            sha256: None,
+        });
+    }
+    let mut hasher = Sha256::new();
+    let bytes = std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(InputDigest {
+        path: path.to_path_buf(),
+        bytes: Some(bytes),
+        sha256: Some(format!("{:x}", hasher.finalize())),
+    })
+}
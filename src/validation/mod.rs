@@ -1,10 +1,18 @@
+use crate::error::ValidatorError;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::path::Path;
 use std::sync::LazyLock;
+pub mod circuit_breaker;
+pub mod concurrency;
 pub mod key_validator;
+pub mod manifest;
+pub mod summary;
 pub mod validation_service;
 
-pub use key_validator::{test_cache_content_api, test_generate_content_api};
+pub use key_validator::{test_cache_content_api, test_generate_content_api, validate_key};
+pub use manifest::{InputDigest, RunManifest};
+pub use summary::ValidationSummary;
 pub use validation_service::{ValidationService, start_validation};
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -44,6 +52,36 @@ pub struct GeminiRequest {
     pub ttl: Option<String>,
 }
 
+/// One page of the ListModels response
+#[derive(Deserialize, Debug)]
+pub struct ModelList {
+    #[serde(default)]
+    pub models: Vec<ModelInfo>,
+    #[serde(rename = "nextPageToken")]
+    pub next_page_token: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ModelInfo {
+    // Resource name, e.g. models/gemini-2.5-flash
+    pub name: String,
+    #[serde(rename = "supportedGenerationMethods", default)]
+    pub supported_generation_methods: Vec<String>,
+}
+
+impl ModelList {
+    /// Whether the list offers generateContent on the given model
+    pub fn supports_generate_content(&self, model: &str) -> bool {
+        self.models.iter().any(|info| {
+            info.name.strip_prefix("models/") == Some(model)
+                && info
+                    .supported_generation_methods
+                    .iter()
+                    .any(|method| method == "generateContent")
+        })
+    }
+}
+
 // LazyLock for the test message body used in API key validation
 pub static GENERATE_CONTENT_TEST_BODY: LazyLock<Value> = LazyLock::new(|| {
     let generate_request = GeminiRequest {
@@ -62,6 +100,19 @@ pub static GENERATE_CONTENT_TEST_BODY: LazyLock<Value> = LazyLock::new(|| {
     serde_json::to_value(generate_request).unwrap()
 });
 
+/// Loads the generateContent test body, from the given JSON file if set or the built-in default
+pub fn load_test_body(path: Option<&Path>) -> Result<Value, ValidatorError> {
+    let Some(path) = path else {
+        return Ok(GENERATE_CONTENT_TEST_BODY.clone());
+    };
+
+    let content = std::fs::read_to_string(path)?;
+    serde_json::from_str(&content).map_err(|source| ValidatorError::TestBodyInvalid {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
 // LazyLock for the cached content test body used in cache API validation
 pub static CACHE_CONTENT_TEST_BODY: LazyLock<GeminiRequest> = LazyLock::new(|| {
     // Generate random text content to meet the minimum 2048 tokens requirement for cache API
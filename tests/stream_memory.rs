@@ -0,0 +1,99 @@
+// Streaming keys from a text file must not hold the whole input in memory. One copy of
+// every distinct key is kept to skip repeats and keep them out of the backup, so memory
+// grows with the distinct keys, within a fixed cost per key, but not with repeated lines.
+// Only one test lives in this binary, so the allocator counts nothing else.
+
+use futures::StreamExt;
+use gemini_keychecker::adapters::{InputOptions, stream_keys_from_txt};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            let current = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(current, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const DISTINCT_KEYS: usize = 100_000;
+
+// Heap kept per distinct key: the string and its slot in the set of seen keys
+const BYTES_PER_DISTINCT_KEY: usize = 100;
+
+fn synthetic_key(index: usize) -> String {
+    format!("AIzaSy{index:0>33}")
+}
+
+// Writes `lines` lines cycling through the first `DISTINCT_KEYS` synthetic keys
+fn write_input(dir: &Path, lines: usize) -> PathBuf {
+    let path = dir.join(format!("keys_{lines}.txt"));
+    let mut file = BufWriter::new(fs::File::create(&path).unwrap());
+    for index in 0..lines {
+        writeln!(file, "{}", synthetic_key(index % DISTINCT_KEYS)).unwrap();
+    }
+    file.flush().unwrap();
+    path
+}
+
+// Streams every key of the input and returns how many were yielded and the
+// peak heap growth in bytes while doing so
+async fn stream_peak(dir: &Path, lines: usize) -> (usize, usize) {
+    let input = write_input(dir, lines);
+    let options = InputOptions {
+        backup_path: dir.join(format!("backup_{lines}.txt")),
+        ..InputOptions::default()
+    };
+
+    let baseline = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(baseline, Ordering::Relaxed);
+    let keys = stream_keys_from_txt(&input, &options).await.unwrap();
+    futures::pin_mut!(keys);
+    let mut yielded = 0;
+    while let Some(key) = keys.next().await {
+        key.unwrap();
+        yielded += 1;
+    }
+    (yielded, PEAK.load(Ordering::Relaxed) - baseline)
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn streaming_memory_grows_only_with_distinct_keys() {
+    let dir = std::env::temp_dir().join(format!("keychecker_stream_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let (distinct_keys, distinct_peak) = stream_peak(&dir, DISTINCT_KEYS).await;
+    let (repeated_keys, repeated_peak) = stream_peak(&dir, 3 * DISTINCT_KEYS).await;
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(distinct_keys, DISTINCT_KEYS);
+    assert_eq!(repeated_keys, DISTINCT_KEYS);
+    assert!(
+        distinct_peak <= DISTINCT_KEYS * BYTES_PER_DISTINCT_KEY,
+        "peak of {distinct_peak} bytes for {DISTINCT_KEYS} distinct keys"
+    );
+    // Three times the lines over the same keys may not cost noticeably more
+    assert!(
+        repeated_peak <= distinct_peak + 64 * 1024,
+        "peak grew from {distinct_peak} to {repeated_peak} bytes"
+    );
+}